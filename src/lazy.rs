@@ -0,0 +1,81 @@
+#[cfg(feature = "owning_ref")]
+use owning_ref::StableAddress;
+use std::{
+    cell::UnsafeCell,
+    ops::Deref,
+    panic::{RefUnwindSafe, UnwindSafe},
+};
+
+use crate::Once;
+
+/// A value that is lazily initialized on first access.
+pub struct Lazy<T, F = fn() -> T> {
+    once: Once<T>,
+    init: UnsafeCell<Option<F>>,
+}
+
+impl<T, F> RefUnwindSafe for Lazy<T, F> {}
+impl<T, F> UnwindSafe for Lazy<T, F> {}
+// `F` is only ever run by whichever thread first calls `force`/`deref` on a
+// shared `&Lazy`, so a `!Send` `F` must not be allowed to cross threads here
+// even though `UnsafeCell<Option<F>>` itself doesn't force that bound.
+unsafe impl<T, F> Send for Lazy<T, F>
+where
+    Once<T>: Send,
+    F: Send,
+{
+}
+unsafe impl<T, F> Sync for Lazy<T, F>
+where
+    Once<T>: Sync,
+    F: Send,
+{
+}
+
+impl<T, F> Lazy<T, F>
+where
+    F: FnOnce() -> T,
+{
+    /// Create a new `Lazy`, deferring the call to `f` until the value is
+    /// first accessed.
+    #[inline]
+    pub const fn new(f: F) -> Self {
+        Self {
+            once: Once::new(),
+            init: UnsafeCell::new(Some(f)),
+        }
+    }
+
+    /// Force the evaluation of this lazy value and return a reference to the
+    /// result. This is equivalent to the `Deref` impl, but is explicit.
+    ///
+    /// # Panics
+    ///
+    /// If the `check` feature is turned on, this panics on the same
+    /// conditions as [`Once::call_once`].
+    #[inline]
+    pub fn force(this: &Self) -> &T {
+        this.once.call_once(|| {
+            let f = unsafe { (*this.init.get()).take() }
+                .expect("Lazy initializer already consumed");
+            f()
+        });
+
+        this.once.get()
+    }
+}
+
+impl<T, F> Deref for Lazy<T, F>
+where
+    F: FnOnce() -> T,
+{
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        Self::force(self)
+    }
+}
+
+#[cfg(feature = "owning_ref")]
+unsafe impl<T, F> StableAddress for Lazy<T, F> where F: FnOnce() -> T {}
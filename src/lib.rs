@@ -1,5 +1,23 @@
+mod lazy;
+#[cfg(any(debug_assertions, feature = "check", loom))]
+mod location;
 mod mutex;
+mod once;
+mod reentrant_mutex;
 mod rwlock;
+#[cfg(any(debug_assertions, feature = "check", loom))]
+mod violation;
 
-pub use mutex::{Mutex, MutexGuard};
-pub use rwlock::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+pub use lazy::Lazy;
+pub use mutex::{MappedMutexGuard, Mutex, MutexGuard};
+pub use once::Once;
+pub use reentrant_mutex::{MappedReentrantMutexGuard, ReentrantMutex, ReentrantMutexGuard};
+pub use rwlock::{
+    MappedRwLockReadGuard, MappedRwLockWriteGuard, RwLock, RwLockReadGuard,
+    RwLockUpgradableReadGuard, RwLockWriteGuard,
+};
+#[cfg(any(debug_assertions, feature = "check", loom))]
+pub use violation::{
+    clear_violation_handler, set_violation_handler, violation_count, LockKind, Operation,
+    ScopedHandler, Violation,
+};
@@ -0,0 +1,79 @@
+//! Diagnostic bookkeeping used by the `check` path: it records which call
+//! site and thread currently hold a lock so that a detected violation can
+//! report both sides of the conflict. This plays no part in the actual
+//! locking protocol and is compiled out entirely in release builds.
+
+use std::{
+    cell::Cell,
+    panic::Location,
+    ptr,
+    sync::atomic::{AtomicPtr, AtomicU64, Ordering},
+};
+
+/// Tracks the `Location` and thread of whoever currently holds a lock.
+#[derive(Debug)]
+pub(crate) struct LockSite {
+    location: AtomicPtr<Location<'static>>,
+    thread: AtomicU64,
+}
+
+impl LockSite {
+    pub(crate) const fn new() -> Self {
+        Self {
+            location: AtomicPtr::new(ptr::null_mut()),
+            thread: AtomicU64::new(0),
+        }
+    }
+
+    /// Record the calling thread as the current holder.
+    pub(crate) fn record(&self, location: &'static Location<'static>) {
+        self.location
+            .store(location as *const _ as *mut _, Ordering::Relaxed);
+        self.thread.store(thread_id(), Ordering::Relaxed);
+    }
+
+    /// Clear the current holder, called on unlock.
+    pub(crate) fn clear(&self) {
+        self.location.store(ptr::null_mut(), Ordering::Relaxed);
+    }
+
+    /// Describe the current holder for a panic message, e.g.
+    /// "src/foo.rs:42:9 by thread #3".
+    pub(crate) fn describe(&self) -> String {
+        let location = self.location.load(Ordering::Relaxed);
+        if location.is_null() {
+            "<unknown>".to_owned()
+        } else {
+            format!(
+                "{} by thread #{}",
+                unsafe { &*location },
+                self.thread.load(Ordering::Relaxed)
+            )
+        }
+    }
+}
+
+/// A small per-process counter standing in for `ThreadId`, since
+/// `ThreadId::as_u64` is not yet stable.
+pub(crate) fn thread_id() -> u64 {
+    #[cfg(not(loom))]
+    thread_local! {
+        static ID: Cell<u64> = const { Cell::new(0) };
+    }
+    // loom runs all of its modeled threads on one OS thread, so a std
+    // thread-local would give every one of them the same id.
+    #[cfg(loom)]
+    loom::thread_local! {
+        static ID: Cell<u64> = Cell::new(0);
+    }
+    static NEXT: AtomicU64 = AtomicU64::new(1);
+
+    ID.with(|id| {
+        let mut current = id.get();
+        if current == 0 {
+            current = NEXT.fetch_add(1, Ordering::Relaxed);
+            id.set(current);
+        }
+        current
+    })
+}
@@ -2,10 +2,22 @@
 use owning_ref::StableAddress;
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
-#[cfg(any(debug_assertions, feature = "check"))]
+#[cfg(any(debug_assertions, feature = "check", loom))]
+use crate::location::LockSite;
+#[cfg(any(debug_assertions, feature = "check", loom))]
+use crate::violation::{self, LockKind, Operation, Violation};
+#[cfg(any(debug_assertions, feature = "check", loom))]
+use std::{panic::Location, time::Instant};
+#[cfg(all(any(debug_assertions, feature = "check", loom), not(loom)))]
 use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(loom)]
+use loom::sync::atomic::{AtomicBool, Ordering};
+#[cfg(loom)]
+use loom::cell::UnsafeCell;
+#[cfg(not(loom))]
+use std::cell::UnsafeCell;
 use std::{
-    cell::UnsafeCell,
+    mem,
     ops::{Deref, DerefMut},
     panic::{RefUnwindSafe, UnwindSafe},
 };
@@ -13,8 +25,10 @@ use std::{
 /// A mutual exclusive lock
 #[derive(Debug)]
 pub struct Mutex<T: ?Sized> {
-    #[cfg(any(debug_assertions, feature = "check"))]
+    #[cfg(any(debug_assertions, feature = "check", loom))]
     state: AtomicBool,
+    #[cfg(any(debug_assertions, feature = "check", loom))]
+    holder: LockSite,
     value: UnsafeCell<T>,
 }
 
@@ -43,8 +57,10 @@ impl<T> Mutex<T> {
     #[inline]
     pub fn new(val: T) -> Self {
         Self {
-            #[cfg(any(debug_assertions, feature = "check"))]
+            #[cfg(any(debug_assertions, feature = "check", loom))]
             state: AtomicBool::new(false),
+            #[cfg(any(debug_assertions, feature = "check", loom))]
+            holder: LockSite::new(),
             value: UnsafeCell::new(val),
         }
     }
@@ -64,55 +80,104 @@ where
     /// have the mutable reference of the lock.
     #[inline]
     pub fn get_mut(&mut self) -> &mut T {
-        self.value.get_mut()
+        self.with_mut(|val| val)
+    }
+
+    // Exclusive access to the protected value. Goes through `loom::cell`
+    // under `cfg(loom)` so the model checker sees the access itself, rather
+    // than just the instant a raw pointer was obtained -- a `Mutex` only
+    // ever hands out one live accessor at a time, so every access here is
+    // exclusive, matching `loom`'s `with_mut`.
+    #[inline]
+    fn with_mut<'s, R>(&'s self, f: impl FnOnce(&'s mut T) -> R) -> R {
+        #[cfg(not(loom))]
+        {
+            f(unsafe { &mut *self.value.get() })
+        }
+
+        #[cfg(loom)]
+        {
+            self.value.with_mut(|ptr| f(unsafe { &mut *ptr }))
+        }
     }
 
     /// Try lock the `Mutex`, returns the mutex guard. Returns None if the
     /// `Mutex` is write locked.
     #[inline]
+    #[track_caller]
     pub fn try_lock<'a>(&'a self) -> Option<MutexGuard<'a, T>> {
-        self.lock_exclusive().then(|| MutexGuard { lock: self })
+        let acquired = self.lock_exclusive();
+        #[cfg(any(debug_assertions, feature = "check", loom))]
+        if acquired {
+            self.holder.record(Location::caller());
+        }
+
+        acquired.then(|| MutexGuard { lock: self })
     }
 
     /// Lock the `Mutex`, returns the mutex guard.
     ///
     /// # Panics
     ///
-    /// If the `Mutex` is already locked, this will panic if the `check` feature
-    /// is turned on.
+    /// If the `Mutex` is already locked, this will report a [`Violation`] to
+    /// the installed violation handler if the `check` feature is turned on,
+    /// which by default panics, naming both the site that currently holds the
+    /// lock and the site attempting this conflicting acquisition. If the
+    /// installed handler does not panic, the CAS is retried (and the
+    /// violation reported again) until the real holder releases the lock --
+    /// a non-panicking handler must never cause this to hand out a guard
+    /// while the lock is still held elsewhere. A conflict that can never
+    /// resolve on its own, such as a same-thread reentrant `lock()`, panics
+    /// outright after a bounded wall-clock budget rather than spinning
+    /// forever.
     #[inline]
+    #[track_caller]
     pub fn lock<'a>(&'a self) -> MutexGuard<'a, T> {
-        if !self.lock_exclusive() {
-            #[cfg(any(debug_assertions, feature = "check"))]
-            panic!("The lock is already write locked")
+        #[cfg(any(debug_assertions, feature = "check", loom))]
+        {
+            let start = Instant::now();
+            while !self.lock_exclusive() {
+                violation::report_retry(
+                    Violation {
+                        kind: LockKind::Mutex,
+                        operation: Operation::Lock,
+                        held: Some((Operation::Lock, self.holder.describe())),
+                        acquirer: Location::caller(),
+                    },
+                    start.elapsed(),
+                );
+            }
         }
 
+        #[cfg(any(debug_assertions, feature = "check", loom))]
+        self.holder.record(Location::caller());
+
         MutexGuard { lock: self }
     }
 
     #[inline]
     fn lock_exclusive(&self) -> bool {
-        #[cfg(any(debug_assertions, feature = "check"))]
+        #[cfg(any(debug_assertions, feature = "check", loom))]
         {
             self.state
                 .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
                 .is_ok()
         }
 
-        #[cfg(not(any(debug_assertions, feature = "check")))]
+        #[cfg(not(any(debug_assertions, feature = "check", loom)))]
         true
     }
 
     #[inline]
     fn unlock_exclusive(&self) -> bool {
-        #[cfg(any(debug_assertions, feature = "check"))]
+        #[cfg(any(debug_assertions, feature = "check", loom))]
         {
             self.state
                 .compare_exchange(true, false, Ordering::Acquire, Ordering::Relaxed)
                 .is_ok()
         }
 
-        #[cfg(not(any(debug_assertions, feature = "check")))]
+        #[cfg(not(any(debug_assertions, feature = "check", loom)))]
         true
     }
 }
@@ -132,7 +197,7 @@ where
 
     #[inline]
     fn deref(&self) -> &T {
-        unsafe { &*self.lock.value.get() }
+        self.lock.with_mut(|val| &*val)
     }
 }
 
@@ -142,7 +207,7 @@ where
 {
     #[inline]
     fn deref_mut(&mut self) -> &mut T {
-        unsafe { &mut *self.lock.value.get() }
+        self.lock.with_mut(|val| val)
     }
 }
 
@@ -152,13 +217,126 @@ where
 {
     #[inline]
     fn drop(&mut self) {
+        #[cfg(any(debug_assertions, feature = "check", loom))]
+        self.lock.holder.clear();
         self.lock.unlock_exclusive();
     }
 }
 
+impl<'a, T> MutexGuard<'a, T>
+where
+    T: ?Sized,
+{
+    /// Make a new `MappedMutexGuard` for a component of the locked data,
+    /// still releasing the original `Mutex` on drop.
+    #[inline]
+    pub fn map<U, F>(this: Self, f: F) -> MappedMutexGuard<'a, T, U>
+    where
+        U: ?Sized,
+        F: FnOnce(&mut T) -> &mut U,
+    {
+        let lock = this.lock;
+        let data = lock.with_mut(|val| f(val) as *mut U);
+        mem::forget(this);
+
+        MappedMutexGuard { lock, data }
+    }
+
+    /// Like [`MutexGuard::map`], but returns the original guard unchanged if
+    /// `f` returns `None`.
+    #[inline]
+    pub fn try_map<U, F>(this: Self, f: F) -> Result<MappedMutexGuard<'a, T, U>, Self>
+    where
+        U: ?Sized,
+        F: FnOnce(&mut T) -> Option<&mut U>,
+    {
+        let lock = this.lock;
+        let data = match lock.with_mut(|val| f(val).map(|data| data as *mut U)) {
+            Some(data) => data,
+            None => return Err(this),
+        };
+        mem::forget(this);
+
+        Ok(MappedMutexGuard { lock, data })
+    }
+}
+
 #[cfg(feature = "owning_ref")]
 unsafe impl<'a, T: 'a> StableAddress for MutexGuard<'a, T> where T: ?Sized {}
 
+/// A guard produced by [`MutexGuard::map`] that projects into a field of the
+/// originally locked value, while still releasing the original `Mutex` on
+/// drop.
+pub struct MappedMutexGuard<'a, T, U>
+where
+    T: ?Sized,
+    U: ?Sized,
+{
+    lock: &'a Mutex<T>,
+    data: *mut U,
+}
+
+impl<'a, T, U> Deref for MappedMutexGuard<'a, T, U>
+where
+    T: ?Sized,
+    U: ?Sized,
+{
+    type Target = U;
+
+    #[inline]
+    fn deref(&self) -> &U {
+        // `data` points inside `lock`'s `UnsafeCell`, so go through
+        // `with_mut` to keep this access inside loom's tracked closure
+        // rather than dereferencing the raw pointer on its own.
+        self.lock.with_mut(|_| unsafe { &*self.data })
+    }
+}
+
+impl<'a, T, U> DerefMut for MappedMutexGuard<'a, T, U>
+where
+    T: ?Sized,
+    U: ?Sized,
+{
+    #[inline]
+    fn deref_mut(&mut self) -> &mut U {
+        self.lock.with_mut(|_| unsafe { &mut *self.data })
+    }
+}
+
+impl<'a, T, U> Drop for MappedMutexGuard<'a, T, U>
+where
+    T: ?Sized,
+    U: ?Sized,
+{
+    #[inline]
+    fn drop(&mut self) {
+        #[cfg(any(debug_assertions, feature = "check", loom))]
+        self.lock.holder.clear();
+        self.lock.unlock_exclusive();
+    }
+}
+
+unsafe impl<'a, T, U> Send for MappedMutexGuard<'a, T, U>
+where
+    T: ?Sized + Send,
+    U: ?Sized + Send,
+{
+}
+unsafe impl<'a, T, U> Sync for MappedMutexGuard<'a, T, U>
+where
+    T: ?Sized + Send,
+    U: ?Sized + Sync,
+{
+}
+
+#[cfg(feature = "owning_ref")]
+unsafe impl<'a, T: 'a, U: 'a> StableAddress for MappedMutexGuard<'a, T, U>
+where
+    T: ?Sized,
+    U: ?Sized,
+{
+}
+
 #[cfg(feature = "serde")]
 impl<T> Serialize for Mutex<T>
 where
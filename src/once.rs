@@ -0,0 +1,127 @@
+#[cfg(any(debug_assertions, feature = "check"))]
+use crate::location::thread_id;
+#[cfg(any(debug_assertions, feature = "check"))]
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+#[cfg(not(any(debug_assertions, feature = "check")))]
+use std::cell::Cell;
+use std::{
+    cell::UnsafeCell,
+    mem::MaybeUninit,
+    panic::{RefUnwindSafe, UnwindSafe},
+    ptr,
+};
+
+#[cfg(any(debug_assertions, feature = "check"))]
+const INCOMPLETE: u8 = 0;
+#[cfg(any(debug_assertions, feature = "check"))]
+const RUNNING: u8 = 1;
+#[cfg(any(debug_assertions, feature = "check"))]
+const COMPLETE: u8 = 2;
+
+/// A cell that can be written to only once.
+pub struct Once<T> {
+    #[cfg(any(debug_assertions, feature = "check"))]
+    state: AtomicU8,
+    #[cfg(any(debug_assertions, feature = "check"))]
+    runner: AtomicU64,
+    #[cfg(not(any(debug_assertions, feature = "check")))]
+    complete: Cell<bool>,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+impl<T> RefUnwindSafe for Once<T> {}
+impl<T> UnwindSafe for Once<T> {}
+unsafe impl<T> Send for Once<T> where T: Send {}
+unsafe impl<T> Sync for Once<T> where T: Send + Sync {}
+
+impl<T> Default for Once<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Once<T> {
+    /// Create a new `Once`, not yet initialized.
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            #[cfg(any(debug_assertions, feature = "check"))]
+            state: AtomicU8::new(INCOMPLETE),
+            #[cfg(any(debug_assertions, feature = "check"))]
+            runner: AtomicU64::new(0),
+            #[cfg(not(any(debug_assertions, feature = "check")))]
+            complete: Cell::new(false),
+            value: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+
+    /// Run `f` to initialize this `Once`, unless it has already been
+    /// initialized. Only the first call actually runs `f`; every later call
+    /// is a no-op.
+    ///
+    /// # Panics
+    ///
+    /// If the `check` feature is turned on, this panics when a concurrent
+    /// call from another thread is still running `f`, or when `f` itself
+    /// calls back into `call_once` on the same `Once`.
+    #[inline]
+    pub fn call_once<F>(&self, f: F)
+    where
+        F: FnOnce() -> T,
+    {
+        #[cfg(any(debug_assertions, feature = "check"))]
+        match self
+            .state
+            .compare_exchange(INCOMPLETE, RUNNING, Ordering::Acquire, Ordering::Relaxed)
+        {
+            Ok(_) => {
+                self.runner.store(thread_id(), Ordering::Relaxed);
+                unsafe { (*self.value.get()).write(f()) };
+                self.state.store(COMPLETE, Ordering::Release);
+            }
+            Err(COMPLETE) => {}
+            Err(RUNNING) if self.runner.load(Ordering::Relaxed) == thread_id() => {
+                panic!("Once::call_once called reentrantly from within its own initializer")
+            }
+            Err(_) => panic!("Once is already being initialized by another thread"),
+        }
+
+        #[cfg(not(any(debug_assertions, feature = "check")))]
+        if !self.complete.get() {
+            unsafe { (*self.value.get()).write(f()) };
+            self.complete.set(true);
+        }
+    }
+
+    /// Get a reference to the inner value.
+    ///
+    /// # Panics
+    ///
+    /// If the `check` feature is turned on, this panics if the `Once` has not
+    /// been initialized yet via `call_once`.
+    #[inline]
+    pub fn get(&self) -> &T {
+        #[cfg(any(debug_assertions, feature = "check"))]
+        assert_eq!(
+            self.state.load(Ordering::Acquire),
+            COMPLETE,
+            "Once::get called before initialization"
+        );
+
+        unsafe { (*self.value.get()).assume_init_ref() }
+    }
+}
+
+impl<T> Drop for Once<T> {
+    #[inline]
+    fn drop(&mut self) {
+        #[cfg(any(debug_assertions, feature = "check"))]
+        let complete = self.state.load(Ordering::Acquire) == COMPLETE;
+        #[cfg(not(any(debug_assertions, feature = "check")))]
+        let complete = self.complete.get();
+
+        if complete {
+            unsafe { ptr::drop_in_place((*self.value.get()).as_mut_ptr()) };
+        }
+    }
+}
@@ -0,0 +1,380 @@
+#[cfg(feature = "owning_ref")]
+use owning_ref::StableAddress;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+#[cfg(any(debug_assertions, feature = "check", loom))]
+use crate::location::{thread_id, LockSite};
+#[cfg(any(debug_assertions, feature = "check", loom))]
+use crate::violation::{self, LockKind, Operation, Violation};
+#[cfg(any(debug_assertions, feature = "check", loom))]
+use std::{panic::Location, time::Instant};
+#[cfg(all(any(debug_assertions, feature = "check", loom), not(loom)))]
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+#[cfg(loom)]
+use loom::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+#[cfg(loom)]
+use loom::cell::UnsafeCell;
+#[cfg(not(loom))]
+use std::cell::UnsafeCell;
+use std::{
+    mem,
+    ops::Deref,
+    panic::{RefUnwindSafe, UnwindSafe},
+};
+
+/// A mutual exclusion lock that may be re-entered by the thread that
+/// currently holds it, while still detecting a *different* thread trying to
+/// acquire it concurrently.
+///
+/// Because an outer and a reentrant acquisition may be alive on the same
+/// thread at once, [`lock`](Self::lock) only ever hands out shared
+/// (`Deref`-only) access through [`ReentrantMutexGuard`], to avoid aliasing
+/// `&mut T`. For mutation, wrap `T` in a type with interior mutability, e.g.
+/// `ReentrantMutex<RefCell<T>>`.
+pub struct ReentrantMutex<T: ?Sized> {
+    #[cfg(any(debug_assertions, feature = "check", loom))]
+    owner: AtomicU64,
+    #[cfg(any(debug_assertions, feature = "check", loom))]
+    count: AtomicUsize,
+    #[cfg(any(debug_assertions, feature = "check", loom))]
+    holder: LockSite,
+    value: UnsafeCell<T>,
+}
+
+impl<T> RefUnwindSafe for ReentrantMutex<T> where T: ?Sized {}
+impl<T> UnwindSafe for ReentrantMutex<T> where T: ?Sized {}
+unsafe impl<T> Send for ReentrantMutex<T> where T: ?Sized + Send {}
+unsafe impl<T> Sync for ReentrantMutex<T> where T: ?Sized + Send + Sync {}
+
+impl<T> From<T> for ReentrantMutex<T> {
+    fn from(val: T) -> Self {
+        Self::new(val)
+    }
+}
+
+impl<T> Default for ReentrantMutex<T>
+where
+    T: ?Sized + Default,
+{
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T> ReentrantMutex<T> {
+    /// Create a new `ReentrantMutex`.
+    #[inline]
+    pub fn new(val: T) -> Self {
+        Self {
+            #[cfg(any(debug_assertions, feature = "check", loom))]
+            owner: AtomicU64::new(0),
+            #[cfg(any(debug_assertions, feature = "check", loom))]
+            count: AtomicUsize::new(0),
+            #[cfg(any(debug_assertions, feature = "check", loom))]
+            holder: LockSite::new(),
+            value: UnsafeCell::new(val),
+        }
+    }
+
+    /// Consume the `ReentrantMutex`, returning the inner value.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+impl<T> ReentrantMutex<T>
+where
+    T: ?Sized,
+{
+    /// Get a mutable reference of the inner value T. This is safe because we
+    /// have the mutable reference of the lock.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.with_mut(|val| val)
+    }
+
+    // Shared access to the protected value, for the (possibly several,
+    // same-thread) outstanding guards. Goes through `loom::cell`'s `with`
+    // under `cfg(loom)` so the model checker sees this as a read, compatible
+    // with the reentrant guards that may coexist on the holding thread --
+    // using `with_mut` here would make `loom` flag those coexisting guards
+    // as a causality violation, since `with_mut` asserts exclusive access.
+    #[inline]
+    fn with_ref<'s, R>(&'s self, f: impl FnOnce(&'s T) -> R) -> R {
+        #[cfg(not(loom))]
+        {
+            f(unsafe { &*self.value.get() })
+        }
+
+        #[cfg(loom)]
+        {
+            self.value.with(|ptr| f(unsafe { &*ptr }))
+        }
+    }
+
+    // Exclusive access to the protected value, for `get_mut`. Goes through
+    // `loom::cell`'s `with_mut` under `cfg(loom)`.
+    #[inline]
+    fn with_mut<'s, R>(&'s self, f: impl FnOnce(&'s mut T) -> R) -> R {
+        #[cfg(not(loom))]
+        {
+            f(unsafe { &mut *self.value.get() })
+        }
+
+        #[cfg(loom)]
+        {
+            self.value.with_mut(|ptr| f(unsafe { &mut *ptr }))
+        }
+    }
+
+    /// Try to lock the `ReentrantMutex`, returns the guard. Returns `None` if
+    /// a different thread currently holds it; always succeeds if the calling
+    /// thread already holds it (reentry).
+    #[inline]
+    #[track_caller]
+    pub fn try_lock<'a>(&'a self) -> Option<ReentrantMutexGuard<'a, T>> {
+        let acquired = self.acquire();
+        #[cfg(any(debug_assertions, feature = "check", loom))]
+        if acquired {
+            self.holder.record(Location::caller());
+        }
+
+        acquired.then(|| ReentrantMutexGuard { lock: self })
+    }
+
+    /// Lock the `ReentrantMutex`, returns the guard. The calling thread may
+    /// call this again while it already holds the lock; each such call must
+    /// be matched by dropping the guard it returns.
+    ///
+    /// # Panics
+    ///
+    /// If a different thread already holds the `ReentrantMutex`, this will
+    /// report a [`Violation`] to the installed violation handler if the
+    /// `check` feature is turned on, which by default panics, naming both
+    /// the site that currently holds the lock and the site attempting this
+    /// conflicting acquisition. If the installed handler does not panic, the
+    /// acquire is retried (and the violation reported again) until the other
+    /// thread releases -- a non-panicking handler must never cause this to
+    /// hand out a guard, and corrupt `owner`/`count`, while a different
+    /// thread still holds the lock. A conflict that can never resolve on its
+    /// own panics outright after a bounded wall-clock budget rather than
+    /// spinning forever.
+    #[inline]
+    #[track_caller]
+    pub fn lock<'a>(&'a self) -> ReentrantMutexGuard<'a, T> {
+        #[cfg(any(debug_assertions, feature = "check", loom))]
+        {
+            let start = Instant::now();
+            while !self.acquire() {
+                violation::report_retry(
+                    Violation {
+                        kind: LockKind::ReentrantMutex,
+                        operation: Operation::Lock,
+                        held: Some((Operation::Lock, self.holder.describe())),
+                        acquirer: Location::caller(),
+                    },
+                    start.elapsed(),
+                );
+            }
+        }
+
+        #[cfg(any(debug_assertions, feature = "check", loom))]
+        self.holder.record(Location::caller());
+
+        ReentrantMutexGuard { lock: self }
+    }
+
+    #[inline]
+    fn acquire(&self) -> bool {
+        #[cfg(any(debug_assertions, feature = "check", loom))]
+        {
+            let tid = thread_id();
+            match self
+                .owner
+                .compare_exchange(0, tid, Ordering::Acquire, Ordering::Relaxed)
+            {
+                Ok(_) => {
+                    self.count.store(1, Ordering::Relaxed);
+                    true
+                }
+                Err(owner) if owner == tid => {
+                    self.count.fetch_add(1, Ordering::Relaxed);
+                    true
+                }
+                Err(_) => false,
+            }
+        }
+
+        #[cfg(not(any(debug_assertions, feature = "check", loom)))]
+        true
+    }
+
+    // Release one level of recursion, clearing ownership once the count
+    // reaches zero. Shared by `ReentrantMutexGuard` and
+    // `MappedReentrantMutexGuard`.
+    #[inline]
+    fn release(&self) {
+        #[cfg(any(debug_assertions, feature = "check", loom))]
+        if self.count.fetch_sub(1, Ordering::Release) == 1 {
+            self.holder.clear();
+            self.owner.store(0, Ordering::Release);
+        }
+    }
+}
+
+pub struct ReentrantMutexGuard<'a, T>
+where
+    T: ?Sized,
+{
+    lock: &'a ReentrantMutex<T>,
+}
+
+impl<'a, T> Deref for ReentrantMutexGuard<'a, T>
+where
+    T: ?Sized,
+{
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        self.lock.with_ref(|val| val)
+    }
+}
+
+impl<'a, T> Drop for ReentrantMutexGuard<'a, T>
+where
+    T: ?Sized,
+{
+    #[inline]
+    fn drop(&mut self) {
+        self.lock.release();
+    }
+}
+
+impl<'a, T> ReentrantMutexGuard<'a, T>
+where
+    T: ?Sized,
+{
+    /// Make a new `MappedReentrantMutexGuard` for a component of the locked
+    /// data, still releasing the original `ReentrantMutex` on drop.
+    #[inline]
+    pub fn map<U, F>(this: Self, f: F) -> MappedReentrantMutexGuard<'a, T, U>
+    where
+        U: ?Sized,
+        F: FnOnce(&T) -> &U,
+    {
+        let lock = this.lock;
+        let data = lock.with_ref(|val| f(val) as *const U);
+        mem::forget(this);
+
+        MappedReentrantMutexGuard { lock, data }
+    }
+
+    /// Like [`ReentrantMutexGuard::map`], but returns the original guard
+    /// unchanged if `f` returns `None`.
+    #[inline]
+    pub fn try_map<U, F>(this: Self, f: F) -> Result<MappedReentrantMutexGuard<'a, T, U>, Self>
+    where
+        U: ?Sized,
+        F: FnOnce(&T) -> Option<&U>,
+    {
+        let lock = this.lock;
+        let data = match lock.with_ref(|val| f(val).map(|data| data as *const U)) {
+            Some(data) => data,
+            None => return Err(this),
+        };
+        mem::forget(this);
+
+        Ok(MappedReentrantMutexGuard { lock, data })
+    }
+}
+
+#[cfg(feature = "owning_ref")]
+unsafe impl<'a, T: 'a> StableAddress for ReentrantMutexGuard<'a, T> where T: ?Sized {}
+
+/// A guard produced by [`ReentrantMutexGuard::map`] that projects into a
+/// field of the originally locked value, while still releasing the original
+/// `ReentrantMutex` on drop.
+pub struct MappedReentrantMutexGuard<'a, T, U>
+where
+    T: ?Sized,
+    U: ?Sized,
+{
+    lock: &'a ReentrantMutex<T>,
+    data: *const U,
+}
+
+impl<'a, T, U> Deref for MappedReentrantMutexGuard<'a, T, U>
+where
+    T: ?Sized,
+    U: ?Sized,
+{
+    type Target = U;
+
+    #[inline]
+    fn deref(&self) -> &U {
+        // `data` points inside `lock`'s `UnsafeCell`, so go through
+        // `with_ref` to keep this access inside loom's tracked closure
+        // rather than dereferencing the raw pointer on its own.
+        self.lock.with_ref(|_| unsafe { &*self.data })
+    }
+}
+
+impl<'a, T, U> Drop for MappedReentrantMutexGuard<'a, T, U>
+where
+    T: ?Sized,
+    U: ?Sized,
+{
+    #[inline]
+    fn drop(&mut self) {
+        self.lock.release();
+    }
+}
+
+unsafe impl<'a, T, U> Send for MappedReentrantMutexGuard<'a, T, U>
+where
+    T: ?Sized + Send + Sync,
+    U: ?Sized + Sync,
+{
+}
+unsafe impl<'a, T, U> Sync for MappedReentrantMutexGuard<'a, T, U>
+where
+    T: ?Sized + Send + Sync,
+    U: ?Sized + Sync,
+{
+}
+
+#[cfg(feature = "owning_ref")]
+unsafe impl<'a, T: 'a, U: 'a> StableAddress for MappedReentrantMutexGuard<'a, T, U>
+where
+    T: ?Sized,
+    U: ?Sized,
+{
+}
+
+#[cfg(feature = "serde")]
+impl<T> Serialize for ReentrantMutex<T>
+where
+    T: Serialize + ?Sized,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.lock().serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T> Deserialize<'de> for ReentrantMutex<T>
+where
+    T: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Deserialize::deserialize(deserializer).map(ReentrantMutex::new)
+    }
+}
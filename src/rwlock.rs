@@ -2,10 +2,22 @@
 use owning_ref::StableAddress;
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
-#[cfg(any(debug_assertions, feature = "check"))]
+#[cfg(any(debug_assertions, feature = "check", loom))]
+use crate::location::LockSite;
+#[cfg(any(debug_assertions, feature = "check", loom))]
+use crate::violation::{self, LockKind, Operation, Violation};
+#[cfg(any(debug_assertions, feature = "check", loom))]
+use std::{panic::Location, time::Instant};
+#[cfg(all(any(debug_assertions, feature = "check", loom), not(loom)))]
 use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(loom)]
+use loom::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(loom)]
+use loom::cell::UnsafeCell;
+#[cfg(not(loom))]
+use std::cell::UnsafeCell;
 use std::{
-    cell::UnsafeCell,
+    mem,
     ops::{Deref, DerefMut},
     panic::{RefUnwindSafe, UnwindSafe},
 };
@@ -13,17 +25,26 @@ use std::{
 // Locking bits are copied from [parking_lot](https://github.com/Amanieu/parking_lot).
 // If the reader count is zero: a writer is currently holding an exclusive lock.
 // Otherwise: a writer is waiting for the remaining readers to exit the lock.
-#[cfg(any(debug_assertions, feature = "check"))]
+#[cfg(any(debug_assertions, feature = "check", loom))]
 const WRITER_BIT: usize = 0b1000;
+// At most one upgradable reader may be held at a time, alongside any number
+// of ordinary readers.
+#[cfg(any(debug_assertions, feature = "check", loom))]
+const UPGRADABLE_BIT: usize = 0b0100;
 // Base unit for counting readers.
-#[cfg(any(debug_assertions, feature = "check"))]
+#[cfg(any(debug_assertions, feature = "check", loom))]
 const ONE_READER: usize = 0b10000;
 
 /// A read-write lock
 #[derive(Debug)]
 pub struct RwLock<T: ?Sized> {
-    #[cfg(any(debug_assertions, feature = "check"))]
+    #[cfg(any(debug_assertions, feature = "check", loom))]
     state: AtomicUsize,
+    // Only the exclusive holder (a writer, or the single upgradable reader)
+    // is tracked: with multiple ordinary readers permitted there is no
+    // single reader site to blame for a conflict.
+    #[cfg(any(debug_assertions, feature = "check", loom))]
+    holder: LockSite,
     value: UnsafeCell<T>,
 }
 
@@ -50,11 +71,26 @@ where
 impl<T> RwLock<T> {
     /// Create a new `RwLock`.
     #[inline]
+    #[cfg(not(loom))]
     pub const fn new(val: T) -> Self {
         Self {
             value: UnsafeCell::new(val),
             #[cfg(any(debug_assertions, feature = "check"))]
             state: AtomicUsize::new(0),
+            #[cfg(any(debug_assertions, feature = "check"))]
+            holder: LockSite::new(),
+        }
+    }
+
+    // loom's atomics carry simulation state that isn't available in const
+    // contexts, so under cfg(loom) this constructor can't be `const fn`.
+    #[inline]
+    #[cfg(loom)]
+    pub fn new(val: T) -> Self {
+        Self {
+            value: UnsafeCell::new(val),
+            state: AtomicUsize::new(0),
+            holder: LockSite::new(),
         }
     }
 
@@ -73,30 +109,94 @@ where
     /// have the mutable reference of the lock.
     #[inline]
     pub fn get_mut(&mut self) -> &mut T {
-        self.value.get_mut()
+        self.with_mut(|val| val)
+    }
+
+    // Shared access to the protected value, for readers and upgradable
+    // readers that may coexist with each other. Goes through `loom::cell`'s
+    // `with` under `cfg(loom)` so the model checker sees this as a read,
+    // compatible with other concurrent readers -- using `with_mut` here
+    // would make `loom` flag two coexisting readers as a causality
+    // violation, since `with_mut` asserts exclusive access.
+    #[inline]
+    fn with_ref<'s, R>(&'s self, f: impl FnOnce(&'s T) -> R) -> R {
+        #[cfg(not(loom))]
+        {
+            f(unsafe { &*self.value.get() })
+        }
+
+        #[cfg(loom)]
+        {
+            self.value.with(|ptr| f(unsafe { &*ptr }))
+        }
+    }
+
+    // Exclusive access to the protected value, for the single writer or the
+    // single upgradable-reader-turned-writer. Goes through `loom::cell`'s
+    // `with_mut` under `cfg(loom)`.
+    #[inline]
+    fn with_mut<'s, R>(&'s self, f: impl FnOnce(&'s mut T) -> R) -> R {
+        #[cfg(not(loom))]
+        {
+            f(unsafe { &mut *self.value.get() })
+        }
+
+        #[cfg(loom)]
+        {
+            self.value.with_mut(|ptr| f(unsafe { &mut *ptr }))
+        }
     }
 
     /// Try write lock the `RwLock`, returns the write guard. Returns None if the
     /// `RwLock` is write locked.
     #[inline]
+    #[track_caller]
     pub fn try_write<'a>(&'a self) -> Option<RwLockWriteGuard<'a, T>> {
-        self.lock_exclusive()
-            .then(|| RwLockWriteGuard { lock: self })
+        let acquired = self.lock_exclusive();
+        #[cfg(any(debug_assertions, feature = "check", loom))]
+        if acquired {
+            self.holder.record(Location::caller());
+        }
+
+        acquired.then(|| RwLockWriteGuard { lock: self })
     }
 
     /// Write lock the `RwLock`, returns the write guard.
     ///
     /// # Panics
     ///
-    /// If the `RwLock` is already write locked, this will panic if the `check`
-    /// feature is turned on.
+    /// If the `RwLock` is already write locked, this will report a
+    /// [`Violation`] to the installed violation handler if the `check`
+    /// feature is turned on, which by default panics, naming both the site
+    /// that currently holds the lock and the site attempting this
+    /// conflicting acquisition. If the installed handler does not panic, the
+    /// CAS is retried (and the violation reported again) until the real
+    /// holder releases the lock -- a non-panicking handler must never cause
+    /// this to hand out a guard while the lock is still held elsewhere. A
+    /// conflict that can never resolve on its own panics outright after a
+    /// bounded wall-clock budget rather than spinning forever.
     #[inline]
+    #[track_caller]
     pub fn write<'a>(&'a self) -> RwLockWriteGuard<'a, T> {
-        if !self.lock_exclusive() {
-            #[cfg(any(debug_assertions, feature = "check"))]
-            panic!("The lock is already write locked")
+        #[cfg(any(debug_assertions, feature = "check", loom))]
+        {
+            let start = Instant::now();
+            while !self.lock_exclusive() {
+                violation::report_retry(
+                    Violation {
+                        kind: LockKind::RwLock,
+                        operation: Operation::Write,
+                        held: self.held_exclusive(),
+                        acquirer: Location::caller(),
+                    },
+                    start.elapsed(),
+                );
+            }
         }
 
+        #[cfg(any(debug_assertions, feature = "check", loom))]
+        self.holder.record(Location::caller());
+
         RwLockWriteGuard { lock: self }
     }
 
@@ -111,47 +211,143 @@ where
     ///
     /// # Panics
     ///
-    /// If the `RwLock` is already write locked, this will panic if the check feature
-    /// is turned on.
+    /// If the `RwLock` is already write locked, this will report a
+    /// [`Violation`] to the installed violation handler if the check feature
+    /// is turned on, which by default panics, naming both the site that
+    /// currently holds the write lock and the site attempting this
+    /// conflicting read. If the installed handler does not panic, the
+    /// acquire is retried (and the violation reported again) until the
+    /// writer releases the lock -- a non-panicking handler must never cause
+    /// this to hand out a read guard while a writer still holds the lock. A
+    /// conflict that can never resolve on its own panics outright after a
+    /// bounded wall-clock budget rather than spinning forever.
     #[inline]
+    #[track_caller]
     pub fn read<'a>(&'a self) -> RwLockReadGuard<'a, T> {
-        if !self.lock_shared() {
-            #[cfg(any(debug_assertions, feature = "check"))]
-            panic!("The lock is already write locked")
+        #[cfg(any(debug_assertions, feature = "check", loom))]
+        {
+            let start = Instant::now();
+            while !self.lock_shared() {
+                violation::report_retry(
+                    Violation {
+                        kind: LockKind::RwLock,
+                        operation: Operation::Read,
+                        // `read()` only ever conflicts with a writer --
+                        // readers and upgradable readers coexist freely.
+                        held: Some((Operation::Write, self.holder.describe())),
+                        acquirer: Location::caller(),
+                    },
+                    start.elapsed(),
+                );
+            }
         }
 
         RwLockReadGuard { lock: self }
     }
 
+    /// Try to acquire an upgradable read lock on the `RwLock`, returning the
+    /// upgradable read guard. Returns None if the `RwLock` is write locked or
+    /// already has an upgradable reader.
+    #[inline]
+    #[track_caller]
+    pub fn try_upgradable_read<'a>(&'a self) -> Option<RwLockUpgradableReadGuard<'a, T>> {
+        let acquired = self.lock_upgradable();
+        #[cfg(any(debug_assertions, feature = "check", loom))]
+        if acquired {
+            self.holder.record(Location::caller());
+        }
+
+        acquired.then(|| RwLockUpgradableReadGuard { lock: self })
+    }
+
+    /// Acquire an upgradable read lock on the `RwLock`, returning the
+    /// upgradable read guard. An upgradable reader derefs like an ordinary
+    /// reader, may coexist with any number of ordinary readers, but can later
+    /// be promoted to a write lock via [`RwLockUpgradableReadGuard::upgrade`].
+    ///
+    /// # Panics
+    ///
+    /// If the `RwLock` is already write locked or already has an upgradable
+    /// reader, this will report a [`Violation`] to the installed violation
+    /// handler if the `check` feature is turned on, which by default panics.
+    /// If the installed handler does not panic, the acquire is retried (and
+    /// the violation reported again) until it succeeds -- a non-panicking
+    /// handler must never cause this to hand out an upgradable guard while
+    /// the conflicting lock is still held elsewhere. A conflict that can
+    /// never resolve on its own panics outright after a bounded wall-clock
+    /// budget rather than spinning forever.
+    #[inline]
+    #[track_caller]
+    pub fn upgradable_read<'a>(&'a self) -> RwLockUpgradableReadGuard<'a, T> {
+        #[cfg(any(debug_assertions, feature = "check", loom))]
+        {
+            let start = Instant::now();
+            while !self.lock_upgradable() {
+                violation::report_retry(
+                    Violation {
+                        kind: LockKind::RwLock,
+                        operation: Operation::UpgradableRead,
+                        held: self.held_exclusive(),
+                        acquirer: Location::caller(),
+                    },
+                    start.elapsed(),
+                );
+            }
+        }
+
+        #[cfg(any(debug_assertions, feature = "check", loom))]
+        self.holder.record(Location::caller());
+
+        RwLockUpgradableReadGuard { lock: self }
+    }
+
+    // What's actually held when `lock_exclusive`/`lock_upgradable` fails its
+    // CAS, so a violation can report the true kind instead of assuming it
+    // matches the attempted operation. `write()` alone can also be blocked by
+    // ordinary readers holding neither bit, which is reported as `None` --
+    // there's no single site to blame for an unspecified number of readers.
+    #[cfg(any(debug_assertions, feature = "check", loom))]
+    #[inline]
+    fn held_exclusive(&self) -> Option<(Operation, String)> {
+        let state = self.state.load(Ordering::Relaxed);
+        if state & WRITER_BIT != 0 {
+            Some((Operation::Write, self.holder.describe()))
+        } else if state & UPGRADABLE_BIT != 0 {
+            Some((Operation::UpgradableRead, self.holder.describe()))
+        } else {
+            None
+        }
+    }
+
     #[inline]
     fn lock_exclusive(&self) -> bool {
-        #[cfg(any(debug_assertions, feature = "check"))]
+        #[cfg(any(debug_assertions, feature = "check", loom))]
         {
             self.state
                 .compare_exchange(0, WRITER_BIT, Ordering::Acquire, Ordering::Relaxed)
                 .is_ok()
         }
 
-        #[cfg(not(any(debug_assertions, feature = "check")))]
+        #[cfg(not(any(debug_assertions, feature = "check", loom)))]
         true
     }
 
     #[inline]
     fn unlock_exclusive(&self) -> bool {
-        #[cfg(any(debug_assertions, feature = "check"))]
+        #[cfg(any(debug_assertions, feature = "check", loom))]
         {
             self.state
                 .compare_exchange(WRITER_BIT, 0, Ordering::Acquire, Ordering::Relaxed)
                 .is_ok()
         }
 
-        #[cfg(not(any(debug_assertions, feature = "check")))]
+        #[cfg(not(any(debug_assertions, feature = "check", loom)))]
         true
     }
 
     #[inline]
     fn lock_shared(&self) -> bool {
-        #[cfg(any(debug_assertions, feature = "check"))]
+        #[cfg(any(debug_assertions, feature = "check", loom))]
         loop {
             let state = self.state.load(Ordering::Relaxed);
             if state & WRITER_BIT != 0 {
@@ -178,9 +374,62 @@ where
 
     #[inline]
     fn unlock_shared(&self) {
-        #[cfg(any(debug_assertions, feature = "check"))]
+        #[cfg(any(debug_assertions, feature = "check", loom))]
         self.state.fetch_sub(ONE_READER, Ordering::Release);
     }
+
+    #[inline]
+    fn lock_upgradable(&self) -> bool {
+        #[cfg(any(debug_assertions, feature = "check", loom))]
+        loop {
+            let state = self.state.load(Ordering::Relaxed);
+            if state & (WRITER_BIT | UPGRADABLE_BIT) != 0 {
+                // is write locked, or already has an upgradable reader
+                return false;
+            }
+
+            if self
+                .state
+                .compare_exchange(
+                    state,
+                    state | UPGRADABLE_BIT,
+                    Ordering::Acquire,
+                    Ordering::Relaxed,
+                )
+                .is_ok()
+            {
+                break;
+            }
+        }
+
+        true
+    }
+
+    #[inline]
+    fn unlock_upgradable(&self) {
+        #[cfg(any(debug_assertions, feature = "check", loom))]
+        self.state.fetch_and(!UPGRADABLE_BIT, Ordering::Release);
+    }
+
+    // Promote the held upgradable reader to an exclusive writer. Only
+    // succeeds if no ordinary readers remain.
+    #[inline]
+    fn upgrade_to_exclusive(&self) -> bool {
+        #[cfg(any(debug_assertions, feature = "check", loom))]
+        {
+            self.state
+                .compare_exchange(
+                    UPGRADABLE_BIT,
+                    WRITER_BIT,
+                    Ordering::Acquire,
+                    Ordering::Relaxed,
+                )
+                .is_ok()
+        }
+
+        #[cfg(not(any(debug_assertions, feature = "check", loom)))]
+        true
+    }
 }
 
 pub struct RwLockWriteGuard<'a, T>
@@ -198,7 +447,7 @@ where
 
     #[inline]
     fn deref(&self) -> &T {
-        unsafe { &*self.lock.value.get() }
+        self.lock.with_mut(|val| &*val)
     }
 }
 
@@ -208,7 +457,7 @@ where
 {
     #[inline]
     fn deref_mut(&mut self) -> &mut T {
-        unsafe { &mut *self.lock.value.get() }
+        self.lock.with_mut(|val| val)
     }
 }
 
@@ -218,10 +467,144 @@ where
 {
     #[inline]
     fn drop(&mut self) {
+        #[cfg(any(debug_assertions, feature = "check", loom))]
+        self.lock.holder.clear();
+        self.lock.unlock_exclusive();
+    }
+}
+
+impl<'a, T> RwLockWriteGuard<'a, T>
+where
+    T: ?Sized,
+{
+    /// Downgrade the write guard to an ordinary read guard, without ever
+    /// releasing the lock to other writers in between.
+    #[inline]
+    pub fn downgrade(self) -> RwLockReadGuard<'a, T> {
+        #[cfg(any(debug_assertions, feature = "check", loom))]
+        self.lock.state.store(ONE_READER, Ordering::Release);
+        #[cfg(any(debug_assertions, feature = "check", loom))]
+        self.lock.holder.clear();
+
+        let lock = self.lock;
+        mem::forget(self);
+        RwLockReadGuard { lock }
+    }
+
+    /// Downgrade the write guard to an upgradable read guard, without ever
+    /// releasing the lock to other writers in between.
+    #[inline]
+    #[track_caller]
+    pub fn downgrade_to_upgradable(self) -> RwLockUpgradableReadGuard<'a, T> {
+        #[cfg(any(debug_assertions, feature = "check", loom))]
+        self.lock.state.store(UPGRADABLE_BIT, Ordering::Release);
+        #[cfg(any(debug_assertions, feature = "check", loom))]
+        self.lock.holder.record(Location::caller());
+
+        let lock = self.lock;
+        mem::forget(self);
+        RwLockUpgradableReadGuard { lock }
+    }
+
+    /// Make a new `MappedRwLockWriteGuard` for a component of the locked
+    /// data, still releasing the original `RwLock` on drop.
+    #[inline]
+    pub fn map<U, F>(this: Self, f: F) -> MappedRwLockWriteGuard<'a, T, U>
+    where
+        U: ?Sized,
+        F: FnOnce(&mut T) -> &mut U,
+    {
+        let lock = this.lock;
+        let data = lock.with_mut(|val| f(val) as *mut U);
+        mem::forget(this);
+
+        MappedRwLockWriteGuard { lock, data }
+    }
+
+    /// Like [`RwLockWriteGuard::map`], but returns the original guard
+    /// unchanged if `f` returns `None`.
+    #[inline]
+    pub fn try_map<U, F>(this: Self, f: F) -> Result<MappedRwLockWriteGuard<'a, T, U>, Self>
+    where
+        U: ?Sized,
+        F: FnOnce(&mut T) -> Option<&mut U>,
+    {
+        let lock = this.lock;
+        let data = match lock.with_mut(|val| f(val).map(|data| data as *mut U)) {
+            Some(data) => data,
+            None => return Err(this),
+        };
+        mem::forget(this);
+
+        Ok(MappedRwLockWriteGuard { lock, data })
+    }
+}
+
+/// A guard produced by [`RwLockWriteGuard::map`] that projects into a field
+/// of the originally locked value, while still releasing the original
+/// `RwLock` on drop.
+pub struct MappedRwLockWriteGuard<'a, T, U>
+where
+    T: ?Sized,
+    U: ?Sized,
+{
+    lock: &'a RwLock<T>,
+    data: *mut U,
+}
+
+impl<'a, T, U> Deref for MappedRwLockWriteGuard<'a, T, U>
+where
+    T: ?Sized,
+    U: ?Sized,
+{
+    type Target = U;
+
+    #[inline]
+    fn deref(&self) -> &U {
+        // `data` points inside `lock`'s `UnsafeCell`, so go through
+        // `with_mut` to keep this access inside loom's tracked closure
+        // rather than dereferencing the raw pointer on its own.
+        self.lock.with_mut(|_| unsafe { &*self.data })
+    }
+}
+
+impl<'a, T, U> DerefMut for MappedRwLockWriteGuard<'a, T, U>
+where
+    T: ?Sized,
+    U: ?Sized,
+{
+    #[inline]
+    fn deref_mut(&mut self) -> &mut U {
+        self.lock.with_mut(|_| unsafe { &mut *self.data })
+    }
+}
+
+impl<'a, T, U> Drop for MappedRwLockWriteGuard<'a, T, U>
+where
+    T: ?Sized,
+    U: ?Sized,
+{
+    #[inline]
+    fn drop(&mut self) {
+        #[cfg(any(debug_assertions, feature = "check", loom))]
+        self.lock.holder.clear();
         self.lock.unlock_exclusive();
     }
 }
 
+unsafe impl<'a, T, U> Send for MappedRwLockWriteGuard<'a, T, U>
+where
+    T: ?Sized + Send + Sync,
+    U: ?Sized + Send,
+{
+}
+unsafe impl<'a, T, U> Sync for MappedRwLockWriteGuard<'a, T, U>
+where
+    T: ?Sized + Send + Sync,
+    U: ?Sized + Sync,
+{
+}
+
 pub struct RwLockReadGuard<'a, T>
 where
     T: ?Sized,
@@ -237,7 +620,7 @@ where
 
     #[inline]
     fn deref(&self) -> &T {
-        unsafe { &*self.lock.value.get() }
+        self.lock.with_ref(|val| val)
     }
 }
 
@@ -251,10 +634,211 @@ where
     }
 }
 
+impl<'a, T> RwLockReadGuard<'a, T>
+where
+    T: ?Sized,
+{
+    /// Make a new `MappedRwLockReadGuard` for a component of the locked
+    /// data, still releasing the original `RwLock` on drop.
+    #[inline]
+    pub fn map<U, F>(this: Self, f: F) -> MappedRwLockReadGuard<'a, T, U>
+    where
+        U: ?Sized,
+        F: FnOnce(&T) -> &U,
+    {
+        let lock = this.lock;
+        let data = lock.with_ref(|val| f(val) as *const U);
+        mem::forget(this);
+
+        MappedRwLockReadGuard { lock, data }
+    }
+
+    /// Like [`RwLockReadGuard::map`], but returns the original guard
+    /// unchanged if `f` returns `None`.
+    #[inline]
+    pub fn try_map<U, F>(this: Self, f: F) -> Result<MappedRwLockReadGuard<'a, T, U>, Self>
+    where
+        U: ?Sized,
+        F: FnOnce(&T) -> Option<&U>,
+    {
+        let lock = this.lock;
+        let data = match lock.with_ref(|val| f(val).map(|data| data as *const U)) {
+            Some(data) => data,
+            None => return Err(this),
+        };
+        mem::forget(this);
+
+        Ok(MappedRwLockReadGuard { lock, data })
+    }
+}
+
+/// A guard produced by [`RwLockReadGuard::map`] that projects into a field
+/// of the originally locked value, while still releasing the original
+/// `RwLock` on drop.
+pub struct MappedRwLockReadGuard<'a, T, U>
+where
+    T: ?Sized,
+    U: ?Sized,
+{
+    lock: &'a RwLock<T>,
+    data: *const U,
+}
+
+impl<'a, T, U> Deref for MappedRwLockReadGuard<'a, T, U>
+where
+    T: ?Sized,
+    U: ?Sized,
+{
+    type Target = U;
+
+    #[inline]
+    fn deref(&self) -> &U {
+        // `data` points inside `lock`'s `UnsafeCell`, so go through
+        // `with_ref` to keep this access inside loom's tracked closure
+        // rather than dereferencing the raw pointer on its own.
+        self.lock.with_ref(|_| unsafe { &*self.data })
+    }
+}
+
+impl<'a, T, U> Drop for MappedRwLockReadGuard<'a, T, U>
+where
+    T: ?Sized,
+    U: ?Sized,
+{
+    #[inline]
+    fn drop(&mut self) {
+        self.lock.unlock_shared();
+    }
+}
+
+unsafe impl<'a, T, U> Send for MappedRwLockReadGuard<'a, T, U>
+where
+    T: ?Sized + Send + Sync,
+    U: ?Sized + Sync,
+{
+}
+unsafe impl<'a, T, U> Sync for MappedRwLockReadGuard<'a, T, U>
+where
+    T: ?Sized + Send + Sync,
+    U: ?Sized + Sync,
+{
+}
+
+/// A guard produced by [`RwLock::upgradable_read`]. Derefs like an ordinary
+/// read guard, but can be promoted to a [`RwLockWriteGuard`] via `upgrade`.
+pub struct RwLockUpgradableReadGuard<'a, T>
+where
+    T: ?Sized,
+{
+    lock: &'a RwLock<T>,
+}
+
+impl<'a, T> RwLockUpgradableReadGuard<'a, T>
+where
+    T: ?Sized,
+{
+    /// Upgrade to a write guard.
+    ///
+    /// # Panics
+    ///
+    /// If other readers are still holding the `RwLock`, this will report a
+    /// [`Violation`] to the installed violation handler if the `check`
+    /// feature is turned on, which by default panics. If the installed
+    /// handler does not panic, the upgrade is retried (and the violation
+    /// reported again) until the other readers release -- a non-panicking
+    /// handler must never cause this to hand out a write guard while readers
+    /// are still present. A conflict that can never resolve on its own
+    /// panics outright after a bounded wall-clock budget rather than
+    /// spinning forever.
+    #[inline]
+    #[track_caller]
+    pub fn upgrade(self) -> RwLockWriteGuard<'a, T> {
+        #[cfg(any(debug_assertions, feature = "check", loom))]
+        {
+            let start = Instant::now();
+            while !self.lock.upgrade_to_exclusive() {
+                violation::report_retry(
+                    Violation {
+                        kind: LockKind::RwLock,
+                        operation: Operation::Upgrade,
+                        held: None,
+                        acquirer: Location::caller(),
+                    },
+                    start.elapsed(),
+                );
+            }
+        }
+
+        #[cfg(any(debug_assertions, feature = "check", loom))]
+        self.lock.holder.record(Location::caller());
+
+        let lock = self.lock;
+        mem::forget(self);
+        RwLockWriteGuard { lock }
+    }
+
+    /// Try to upgrade to a write guard, returning the upgradable read guard
+    /// back if other readers are still holding the `RwLock`.
+    #[inline]
+    #[track_caller]
+    pub fn try_upgrade(self) -> Result<RwLockWriteGuard<'a, T>, Self> {
+        if !self.lock.upgrade_to_exclusive() {
+            return Err(self);
+        }
+
+        #[cfg(any(debug_assertions, feature = "check", loom))]
+        self.lock.holder.record(Location::caller());
+
+        let lock = self.lock;
+        mem::forget(self);
+        Ok(RwLockWriteGuard { lock })
+    }
+}
+
+impl<'a, T> Deref for RwLockUpgradableReadGuard<'a, T>
+where
+    T: ?Sized,
+{
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        self.lock.with_ref(|val| val)
+    }
+}
+
+impl<'a, T> Drop for RwLockUpgradableReadGuard<'a, T>
+where
+    T: ?Sized,
+{
+    #[inline]
+    fn drop(&mut self) {
+        #[cfg(any(debug_assertions, feature = "check", loom))]
+        self.lock.holder.clear();
+        self.lock.unlock_upgradable();
+    }
+}
+
 #[cfg(feature = "owning_ref")]
 unsafe impl<'a, T: 'a> StableAddress for RwLockReadGuard<'a, T> where T: ?Sized {}
 #[cfg(feature = "owning_ref")]
 unsafe impl<'a, T: 'a> StableAddress for RwLockWriteGuard<'a, T> where T: ?Sized {}
+#[cfg(feature = "owning_ref")]
+unsafe impl<'a, T: 'a> StableAddress for RwLockUpgradableReadGuard<'a, T> where T: ?Sized {}
+#[cfg(feature = "owning_ref")]
+unsafe impl<'a, T: 'a, U: 'a> StableAddress for MappedRwLockReadGuard<'a, T, U>
+where
+    T: ?Sized,
+    U: ?Sized,
+{
+}
+#[cfg(feature = "owning_ref")]
+unsafe impl<'a, T: 'a, U: 'a> StableAddress for MappedRwLockWriteGuard<'a, T, U>
+where
+    T: ?Sized,
+    U: ?Sized,
+{
+}
 
 #[cfg(feature = "serde")]
 impl<T> Serialize for RwLock<T>
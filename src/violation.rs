@@ -0,0 +1,182 @@
+//! Pluggable reporting for detected lock violations.
+//!
+//! A conflict is fatal by default: the default handler panics, matching the
+//! crate's original behavior. Installing a different handler via
+//! [`set_violation_handler`] or [`ScopedHandler`] lets a test suite collect
+//! every violation across a run instead of aborting on the first one.
+
+use std::{
+    fmt,
+    panic::Location,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, RwLock,
+    },
+    thread,
+    time::Duration,
+};
+
+/// The kind of lock a violation was detected on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockKind {
+    Mutex,
+    RwLock,
+    ReentrantMutex,
+}
+
+/// The operation that triggered a violation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Lock,
+    Read,
+    Write,
+    UpgradableRead,
+    Upgrade,
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Operation::Lock => "lock",
+            Operation::Read => "read lock",
+            Operation::Write => "write lock",
+            Operation::UpgradableRead => "upgradable read lock",
+            Operation::Upgrade => "upgrade",
+        };
+        f.write_str(s)
+    }
+}
+
+/// A detected lock conflict, reported to whatever handler is currently
+/// installed via [`set_violation_handler`].
+#[derive(Debug, Clone)]
+pub struct Violation {
+    pub kind: LockKind,
+    pub operation: Operation,
+    /// What is actually held, and a description of the site holding it, if
+    /// there is a single site to blame. This is independent of `operation`:
+    /// e.g. a `read()` conflicts with a held `write()`, not another `read()`.
+    /// Absent when the conflict is with an unspecified number of ordinary
+    /// readers, as with a failed [`Operation::Upgrade`].
+    pub held: Option<(Operation, String)>,
+    pub acquirer: &'static Location<'static>,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.held {
+            Some((held, holder)) => write!(
+                f,
+                "the {:?} is already {} (held at {}, conflicting {} at {})",
+                self.kind, held, holder, self.operation, self.acquirer
+            ),
+            None => write!(
+                f,
+                "cannot {} the {:?}: other readers are still present (conflicting attempt at {})",
+                self.operation, self.kind, self.acquirer
+            ),
+        }
+    }
+}
+
+type Handler = Arc<dyn Fn(Violation) + Send + Sync>;
+
+static VIOLATION_COUNT: AtomicUsize = AtomicUsize::new(0);
+static HANDLER: RwLock<Option<Handler>> = RwLock::new(None);
+
+fn default_handler(violation: Violation) {
+    panic!("{violation}");
+}
+
+// Report a detected violation to the installed handler, falling back to a
+// panic if none is installed. Every call increments `violation_count`,
+// regardless of what the handler does with it.
+//
+// The `HANDLER` read guard is dropped before `handler` runs (the `Arc` is
+// cloned out first), so a handler that itself calls
+// `set_violation_handler`/`clear_violation_handler` -- a natural thing for a
+// "stop after N violations" handler to do -- doesn't self-deadlock on
+// `HANDLER`'s non-reentrant write path.
+pub(crate) fn report(violation: Violation) {
+    VIOLATION_COUNT.fetch_add(1, Ordering::Relaxed);
+    let handler = HANDLER.read().unwrap().clone();
+    match handler {
+        Some(handler) => handler(violation),
+        None => default_handler(violation),
+    }
+}
+
+/// How long a call site will keep retrying an acquire after a non-panicking
+/// handler lets [`report`] return, before giving up and panicking outright.
+///
+/// Retrying is only correct when the conflicting holder can eventually
+/// release -- a same-thread reentrant conflict (e.g. calling
+/// [`Mutex::lock`](crate::Mutex::lock) again on the thread that already
+/// holds it) never will, and would otherwise busy-spin forever. This bounds
+/// that livelock into a diagnosable panic instead. Wall-clock time, rather
+/// than a retry count, is what actually matters here: how many attempts a
+/// genuine (resolvable) wait takes depends entirely on how long the other
+/// side holds the lock.
+const MAX_RETRY_DURATION: Duration = Duration::from_secs(2);
+
+// Like `report`, but for a retry loop that keeps reporting the same
+// violation until the acquire succeeds: panics once `waited` reaches
+// `MAX_RETRY_DURATION`, regardless of the installed handler, so a livelock
+// ends in a panic instead of spinning forever. Yields between attempts so a
+// non-panicking handler retrying against a real, eventually-releasing
+// holder doesn't pin a CPU core the whole time.
+pub(crate) fn report_retry(violation: Violation, waited: Duration) {
+    if waited >= MAX_RETRY_DURATION {
+        panic!(
+            "exceeded {MAX_RETRY_DURATION:?} retrying an acquire after repeated violations, \
+             and the installed handler never panicked -- this is likely a conflict that can \
+             never resolve on its own (e.g. a same-thread reentrant acquire): {violation}"
+        );
+    }
+
+    report(violation);
+    thread::yield_now();
+}
+
+/// Install a global handler invoked whenever peace-lock detects a conflicting
+/// lock acquisition, in place of the default panic.
+pub fn set_violation_handler(handler: Box<dyn Fn(Violation) + Send + Sync>) {
+    *HANDLER.write().unwrap() = Some(Arc::from(handler));
+}
+
+/// Remove any installed handler, restoring the default panicking behavior.
+pub fn clear_violation_handler() {
+    *HANDLER.write().unwrap() = None;
+}
+
+/// The number of violations reported so far across all locks, regardless of
+/// what the installed handler chose to do with each one.
+pub fn violation_count() -> usize {
+    VIOLATION_COUNT.load(Ordering::Relaxed)
+}
+
+/// Installs a violation handler for the lifetime of this guard, restoring
+/// whatever handler was previously installed on drop. Handy for giving a
+/// test its own view of the violations it triggers.
+///
+/// The installed handler is one `static` shared by the whole process: tests
+/// using `ScopedHandler` must not run concurrently with each other, or with
+/// anything else that depends on the default panicking handler, or they will
+/// stomp each other's installed handler mid-run. Serialize them, e.g. behind
+/// a `Mutex` held for the scope's duration, or with `--test-threads=1`.
+pub struct ScopedHandler {
+    previous: Option<Handler>,
+}
+
+impl ScopedHandler {
+    pub fn new(handler: Box<dyn Fn(Violation) + Send + Sync>) -> Self {
+        let previous = HANDLER.write().unwrap().replace(Arc::from(handler));
+        ScopedHandler { previous }
+    }
+}
+
+impl Drop for ScopedHandler {
+    fn drop(&mut self) {
+        *HANDLER.write().unwrap() = self.previous.take();
+    }
+}
@@ -0,0 +1,235 @@
+#![cfg(loom)]
+
+use loom::sync::atomic::{AtomicBool, Ordering};
+use loom::thread;
+use peace_lock::{Mutex, MutexGuard, ReentrantMutex, RwLock, RwLockWriteGuard};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::Arc;
+
+// `Mutex`/`RwLock` in check mode are single-attempt conflict *detectors*,
+// not blocking locks: a genuine race between two threads' CAS attempts is
+// expected to trip the violation handler (by default a panic), so these
+// tests hand off the critical section through a flag instead of racing on
+// it directly. That still lets loom explore every interleaving of the
+// underlying `compare_exchange`/`fetch_*` memory operations across the
+// handoff, checking that the CAS state machine never loses an update or
+// otherwise corrupts the handed-off state.
+
+#[test]
+fn mutex_handoff_never_loses_an_update() {
+    loom::model(|| {
+        let val = Arc::new(Mutex::new(0));
+        let handed_off = Arc::new(AtomicBool::new(false));
+
+        let a = {
+            let val = Arc::clone(&val);
+            let handed_off = Arc::clone(&handed_off);
+            thread::spawn(move || {
+                let mut guard = val.lock();
+                *guard += 1;
+                drop(guard);
+                handed_off.store(true, Ordering::Release);
+            })
+        };
+
+        let b = {
+            let val = Arc::clone(&val);
+            let handed_off = Arc::clone(&handed_off);
+            thread::spawn(move || {
+                while !handed_off.load(Ordering::Acquire) {
+                    thread::yield_now();
+                }
+                let mut guard = val.lock();
+                *guard += 1;
+            })
+        };
+
+        a.join().unwrap();
+        b.join().unwrap();
+
+        assert_eq!(*val.lock(), 2);
+    });
+}
+
+#[test]
+fn rwlock_handoff_never_loses_an_update() {
+    loom::model(|| {
+        let val = Arc::new(RwLock::new(0));
+        let handed_off = Arc::new(AtomicBool::new(false));
+
+        let writer = {
+            let val = Arc::clone(&val);
+            let handed_off = Arc::clone(&handed_off);
+            thread::spawn(move || {
+                let mut guard = val.write();
+                *guard += 1;
+                drop(guard);
+                handed_off.store(true, Ordering::Release);
+            })
+        };
+
+        let reader = {
+            let val = Arc::clone(&val);
+            let handed_off = Arc::clone(&handed_off);
+            thread::spawn(move || {
+                while !handed_off.load(Ordering::Acquire) {
+                    thread::yield_now();
+                }
+                let guard = val.read();
+                assert_eq!(*guard, 1);
+            })
+        };
+
+        writer.join().unwrap();
+        reader.join().unwrap();
+    });
+}
+
+// `ReentrantMutex` is check-mode too: a genuine cross-thread race on
+// `owner`/`count` is expected to trip the violation handler, so this hands
+// off the critical section through a flag just like the `Mutex` test above.
+// Thread `a` additionally re-enters the lock on the same thread before
+// releasing, exercising the `count` fetch_add/fetch_sub side of the CAS
+// state machine in `acquire`/`release` alongside the `owner` handoff.
+//
+// `RefCell` isn't `Sync`, so `ReentrantMutex<RefCell<_>>` isn't either --
+// shared through an `Rc` rather than an `Arc`, which loom's mock
+// `thread::spawn` (unlike `std::thread::spawn`) doesn't require.
+#[test]
+fn reentrant_mutex_reentry_and_handoff_never_loses_an_update() {
+    loom::model(|| {
+        let val = Rc::new(ReentrantMutex::new(RefCell::new(0)));
+        let handed_off = Arc::new(AtomicBool::new(false));
+
+        let a = {
+            let val = Rc::clone(&val);
+            let handed_off = Arc::clone(&handed_off);
+            thread::spawn(move || {
+                let outer = val.lock();
+                {
+                    let inner = val.lock();
+                    *inner.borrow_mut() += 1;
+                }
+                drop(outer);
+                handed_off.store(true, Ordering::Release);
+            })
+        };
+
+        let b = {
+            let val = Rc::clone(&val);
+            let handed_off = Arc::clone(&handed_off);
+            thread::spawn(move || {
+                while !handed_off.load(Ordering::Acquire) {
+                    thread::yield_now();
+                }
+                let guard = val.lock();
+                *guard.borrow_mut() += 1;
+            })
+        };
+
+        a.join().unwrap();
+        b.join().unwrap();
+
+        assert_eq!(*val.lock().borrow(), 2);
+    });
+}
+
+// Two readers may always coexist: neither ever panics, and both observe the
+// `ONE_READER` counter consistently regardless of interleaving.
+#[test]
+fn rwlock_two_readers_coexist() {
+    loom::model(|| {
+        let val = Arc::new(RwLock::new(0));
+
+        let threads: Vec<_> = (0..2)
+            .map(|_| {
+                let val = Arc::clone(&val);
+                thread::spawn(move || {
+                    let guard = val.read();
+                    assert_eq!(*guard, 0);
+                })
+            })
+            .collect();
+
+        for t in threads {
+            t.join().unwrap();
+        }
+    });
+}
+
+// A mapped guard's `Deref`/`DerefMut` must keep every access to the
+// projected field inside loom's tracked closure, same as the unmapped
+// guards above -- otherwise the handoff below could race undetected.
+#[test]
+fn mapped_mutex_guard_handoff_never_loses_an_update() {
+    loom::model(|| {
+        let val = Arc::new(Mutex::new((0, 0)));
+        let handed_off = Arc::new(AtomicBool::new(false));
+
+        let a = {
+            let val = Arc::clone(&val);
+            let handed_off = Arc::clone(&handed_off);
+            thread::spawn(move || {
+                let mut guard = MutexGuard::map(val.lock(), |pair| &mut pair.0);
+                *guard += 1;
+                drop(guard);
+                handed_off.store(true, Ordering::Release);
+            })
+        };
+
+        let b = {
+            let val = Arc::clone(&val);
+            let handed_off = Arc::clone(&handed_off);
+            thread::spawn(move || {
+                while !handed_off.load(Ordering::Acquire) {
+                    thread::yield_now();
+                }
+                let mut guard = MutexGuard::map(val.lock(), |pair| &mut pair.0);
+                *guard += 1;
+            })
+        };
+
+        a.join().unwrap();
+        b.join().unwrap();
+
+        assert_eq!(val.lock().0, 2);
+    });
+}
+
+// Same as above, for `RwLockWriteGuard::map`.
+#[test]
+fn mapped_rwlock_write_guard_handoff_never_loses_an_update() {
+    loom::model(|| {
+        let val = Arc::new(RwLock::new((0, 0)));
+        let handed_off = Arc::new(AtomicBool::new(false));
+
+        let a = {
+            let val = Arc::clone(&val);
+            let handed_off = Arc::clone(&handed_off);
+            thread::spawn(move || {
+                let mut guard = RwLockWriteGuard::map(val.write(), |pair| &mut pair.0);
+                *guard += 1;
+                drop(guard);
+                handed_off.store(true, Ordering::Release);
+            })
+        };
+
+        let b = {
+            let val = Arc::clone(&val);
+            let handed_off = Arc::clone(&handed_off);
+            thread::spawn(move || {
+                while !handed_off.load(Ordering::Acquire) {
+                    thread::yield_now();
+                }
+                let mut guard = RwLockWriteGuard::map(val.write(), |pair| &mut pair.0);
+                *guard += 1;
+            })
+        };
+
+        a.join().unwrap();
+        b.join().unwrap();
+
+        assert_eq!(val.read().0, 2);
+    });
+}
@@ -1,4 +1,4 @@
-use peace_lock::Mutex;
+use peace_lock::{Mutex, MutexGuard};
 use std::{thread, thread::sleep, time::Duration};
 
 #[test]
@@ -34,3 +34,24 @@ fn double_lock() {
         });
     });
 }
+
+#[test]
+fn mapped_guard_projects_field() {
+    let val = Mutex::new((1, 2));
+    let guard = val.lock();
+    let mut mapped = MutexGuard::map(guard, |pair| &mut pair.0);
+    *mapped += 1;
+    drop(mapped);
+
+    assert_eq!(*val.lock(), (2, 2));
+}
+
+#[test]
+#[should_panic]
+fn mapped_guard_still_locks() {
+    let val = Mutex::new((1, 2));
+    let guard = val.lock();
+    let _mapped = MutexGuard::map(guard, |pair| &mut pair.0);
+
+    let _lock2 = val.lock();
+}
@@ -0,0 +1,60 @@
+use peace_lock::{Lazy, Once};
+use std::{thread, thread::sleep, time::Duration};
+
+#[test]
+fn call_once_runs_once() {
+    let once = Once::new();
+    let mut calls = 0;
+    once.call_once(|| {
+        calls += 1;
+        calls
+    });
+    once.call_once(|| {
+        calls += 1;
+        calls
+    });
+    assert_eq!(*once.get(), 1);
+}
+
+#[test]
+#[should_panic]
+fn get_before_init_panics() {
+    let once = Once::<i32>::new();
+    once.get();
+}
+
+#[test]
+#[should_panic]
+fn reentrant_call_once_panics() {
+    let once = Once::new();
+    once.call_once(|| {
+        once.call_once(|| 1);
+        1
+    });
+}
+
+#[test]
+#[should_panic]
+fn concurrent_call_once_panics() {
+    let once = Once::new();
+    thread::scope(|s| {
+        s.spawn(|| {
+            once.call_once(|| {
+                sleep(Duration::from_secs(2));
+                1
+            });
+        });
+
+        s.spawn(|| {
+            sleep(Duration::from_secs(1));
+            once.call_once(|| 2);
+        });
+    });
+}
+
+#[test]
+fn lazy_forces_once() {
+    let lazy = Lazy::new(|| 7);
+    assert_eq!(*lazy, 7);
+    assert_eq!(*lazy, 7);
+}
@@ -0,0 +1,70 @@
+use peace_lock::{ReentrantMutex, ReentrantMutexGuard};
+use std::{thread, thread::sleep, time::Duration};
+
+#[test]
+fn reentrant_lock_same_thread() {
+    let val = ReentrantMutex::new(1);
+    let outer = val.lock();
+    let inner = val.lock();
+    assert_eq!(*inner, 1);
+    drop(inner);
+    assert_eq!(*outer, 1);
+}
+
+#[test]
+fn lock_unlock_lock() {
+    let val = ReentrantMutex::new(1);
+    thread::scope(|s| {
+        s.spawn(|| {
+            let lock1 = val.lock();
+            drop(lock1);
+            sleep(Duration::from_secs(1));
+        });
+
+        s.spawn(|| {
+            sleep(Duration::from_secs(1));
+            let _lock2 = val.lock();
+        });
+    });
+}
+
+#[test]
+#[should_panic]
+fn cross_thread_lock_conflicts() {
+    let val = ReentrantMutex::new(1);
+    thread::scope(|s| {
+        s.spawn(|| {
+            let _lock1 = val.lock();
+            sleep(Duration::from_secs(1));
+        });
+
+        s.spawn(|| {
+            let _lock2 = val.lock();
+            sleep(Duration::from_secs(1));
+        });
+    });
+}
+
+#[test]
+fn try_lock_fails_while_another_thread_holds_it() {
+    let val = ReentrantMutex::new(1);
+    thread::scope(|s| {
+        s.spawn(|| {
+            let _lock1 = val.lock();
+            sleep(Duration::from_secs(1));
+        });
+
+        s.spawn(|| {
+            sleep(Duration::from_millis(200));
+            assert!(val.try_lock().is_none());
+        });
+    });
+}
+
+#[test]
+fn mapped_guard_projects_field() {
+    let val = ReentrantMutex::new((1, 2));
+    let guard = val.lock();
+    let mapped = ReentrantMutexGuard::map(guard, |pair| &pair.1);
+    assert_eq!(*mapped, 2);
+}
@@ -1,4 +1,4 @@
-use peace_lock::RwLock;
+use peace_lock::{RwLock, RwLockReadGuard, RwLockWriteGuard};
 use std::{thread, thread::sleep, time::Duration};
 
 #[test]
@@ -84,3 +84,81 @@ fn multiple_read() {
         });
     });
 }
+
+#[test]
+fn upgradable_read_coexists_with_readers() {
+    let val = RwLock::new(1);
+    thread::scope(|s| {
+        s.spawn(|| {
+            let _lock1 = val.upgradable_read();
+            sleep(Duration::from_secs(2));
+        });
+
+        s.spawn(|| {
+            sleep(Duration::from_secs(1));
+            let _lock2 = val.read();
+        });
+    });
+}
+
+#[test]
+#[should_panic]
+fn double_upgradable_read_conflict() {
+    let val = RwLock::new(1);
+    thread::scope(|s| {
+        s.spawn(|| {
+            let _lock1 = val.upgradable_read();
+            sleep(Duration::from_secs(2));
+        });
+
+        s.spawn(|| {
+            sleep(Duration::from_secs(1));
+            let _lock2 = val.upgradable_read();
+        });
+    });
+}
+
+#[test]
+fn upgrade_then_downgrade() {
+    let val = RwLock::new(1);
+    let upgradable = val.upgradable_read();
+    assert_eq!(*upgradable, 1);
+
+    let mut write = upgradable.upgrade();
+    *write = 2;
+
+    let read = write.downgrade();
+    assert_eq!(*read, 2);
+}
+
+#[test]
+fn try_upgrade_fails_with_other_readers() {
+    let val = RwLock::new(1);
+    let upgradable = val.upgradable_read();
+    let _reader = val.read();
+
+    let upgradable = match upgradable.try_upgrade() {
+        Ok(_) => panic!("expected try_upgrade to fail while a reader is present"),
+        Err(upgradable) => upgradable,
+    };
+    assert_eq!(*upgradable, 1);
+}
+
+#[test]
+fn mapped_write_guard_projects_field() {
+    let val = RwLock::new((1, 2));
+    let guard = val.write();
+    let mut mapped = RwLockWriteGuard::map(guard, |pair| &mut pair.0);
+    *mapped += 1;
+    drop(mapped);
+
+    assert_eq!(*val.read(), (2, 2));
+}
+
+#[test]
+fn mapped_read_guard_projects_field() {
+    let val = RwLock::new((1, 2));
+    let guard = val.read();
+    let mapped = RwLockReadGuard::map(guard, |pair| &pair.1);
+    assert_eq!(*mapped, 2);
+}
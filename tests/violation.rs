@@ -0,0 +1,63 @@
+#![cfg(any(debug_assertions, feature = "check"))]
+
+use peace_lock::{violation_count, Mutex, ScopedHandler};
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex as StdMutex,
+    },
+    thread,
+    thread::sleep,
+    time::Duration,
+};
+
+// The violation handler is one process-wide `static`, so tests that install
+// one must not run concurrently with each other -- serialize them behind
+// this lock for the scope of the installed handler.
+static SERIALIZE: StdMutex<()> = StdMutex::new(());
+
+#[test]
+fn counting_handler_observes_conflict_without_panicking() {
+    static SEEN: AtomicUsize = AtomicUsize::new(0);
+
+    let _serialize = SERIALIZE.lock().unwrap();
+    let _scope = ScopedHandler::new(Box::new(|_violation| {
+        SEEN.fetch_add(1, Ordering::Relaxed);
+    }));
+    let before = violation_count();
+
+    let val = Mutex::new(1);
+    thread::scope(|s| {
+        s.spawn(|| {
+            let _lock1 = val.lock();
+            sleep(Duration::from_millis(300));
+        });
+
+        s.spawn(|| {
+            sleep(Duration::from_millis(100));
+            // The other thread is still holding the lock: this call must
+            // retry the installed (non-panicking) handler rather than
+            // hanging or handing out a guard while `_lock1` is still live,
+            // and must return once `_lock1` is actually dropped.
+            let _lock2 = val.lock();
+        });
+    });
+
+    assert!(violation_count() > before);
+    assert!(SEEN.load(Ordering::Relaxed) > 0);
+}
+
+#[test]
+#[should_panic]
+fn unbounded_retry_is_bounded_into_a_panic() {
+    let _serialize = SERIALIZE.lock().unwrap();
+    let _scope = ScopedHandler::new(Box::new(|_violation| {
+        // Swallow every violation: since this is a same-thread reentrant
+        // conflict, the retry loop can never succeed on its own and must
+        // eventually panic rather than spin forever.
+    }));
+
+    let val = Mutex::new(1);
+    let _outer = val.lock();
+    let _inner = val.lock();
+}